@@ -0,0 +1,82 @@
+//! Proc-macro support for `config_playground`.
+//!
+//! Provides `#[derive(ConfigSource)]`, which generates an `impl
+//! crate::ConfigSource` for a struct, returning a `crate::SourceMap` (which
+//! implements `config::Source`) so it can be fed into a `config::Config`
+//! builder directly, instead of round-tripping through a serialized string.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Lit, Meta, NestedMeta};
+
+/// Derives `ConfigSource` for a struct.
+///
+/// Each field becomes an entry in the resulting map, keyed by its (optionally
+/// renamed) field name. `Option<T>` fields are skipped entirely when `None`,
+/// mirroring the `#[skip_serializing_none]` behavior this replaces. A field's
+/// key can be overridden with `#[config(key = "...")]`.
+#[proc_macro_derive(ConfigSource, attributes(config))]
+pub fn derive_config_source(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => panic!("ConfigSource can only be derived for structs with named fields"),
+        },
+        _ => panic!("ConfigSource can only be derived for structs"),
+    };
+
+    let inserts = fields.iter().map(|field| {
+        let ident = field.ident.as_ref().expect("named field");
+        let key = config_key(field).unwrap_or_else(|| ident.to_string());
+        let is_optional = is_option(&field.ty);
+
+        if is_optional {
+            quote! {
+                if let Some(ref value) = self.#ident {
+                    map.insert(#key.to_string(), config::Value::from(value.clone()));
+                }
+            }
+        } else {
+            quote! {
+                map.insert(#key.to_string(), config::Value::from(self.#ident.clone()));
+            }
+        }
+    });
+
+    let expanded = quote! {
+        impl crate::ConfigSource for #name {
+            fn into_source(&self) -> crate::SourceMap {
+                let mut map = std::collections::HashMap::new();
+                #(#inserts)*
+                crate::SourceMap(map)
+            }
+        }
+    };
+
+    TokenStream::from(expanded)
+}
+
+/// Reads a `#[config(key = "...")]` rename attribute off a field, if present.
+fn config_key(field: &syn::Field) -> Option<String> {
+    field.attrs.iter().find_map(|attr| {
+        if !attr.path.is_ident("config") {
+            return None;
+        }
+        let meta = attr.parse_meta().ok()?;
+        let Meta::List(list) = meta else { return None };
+        list.nested.into_iter().find_map(|nested| match nested {
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("key") => match nv.lit {
+                Lit::Str(s) => Some(s.value()),
+                _ => None,
+            },
+            _ => None,
+        })
+    })
+}
+
+fn is_option(ty: &syn::Type) -> bool {
+    matches!(ty, syn::Type::Path(p) if p.path.segments.last().is_some_and(|s| s.ident == "Option"))
+}
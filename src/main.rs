@@ -1,26 +1,71 @@
 use anyhow::Context;
-use clap::{Args, Parser};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use config_playground_derive::ConfigSource;
 use const_str::convert_ascii_case;
+use directories::ProjectDirs;
 use secrecy::{ExposeSecret, Secret};
+use serde::Serializer;
 use serde_aux::field_attributes::deserialize_number_from_string;
 use serde_with::skip_serializing_none;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
-#[derive(serde::Deserialize, Clone, Debug)]
+/// Env-var prefix for this app's config (the shouty-snake cargo package name).
+static APP_NAME: &str = convert_ascii_case!(shouty_snake, std::env!("CARGO_PKG_NAME"));
+
+/// Implemented by structs that can feed a `config::Config` builder directly,
+/// without an intermediate serialize/parse round-trip. See `#[derive(ConfigSource)]`.
+trait ConfigSource {
+    /// Flattens `self` into a map of dotted config paths to values, skipping
+    /// any field that has no value to contribute (e.g. a `None` optional).
+    // Named to read naturally at the `add_source(x.into_source())` call site
+    // rather than to follow the `into_`-takes-`self`-by-value convention.
+    #[allow(clippy::wrong_self_convention)]
+    fn into_source(&self) -> SourceMap;
+}
+
+/// A flattened config map that implements `config::Source` directly, so
+/// `#[derive(ConfigSource)]` output can be handed to `add_source` without an
+/// intermediate serialize/parse round-trip (a bare `HashMap` doesn't implement
+/// `Source`).
+#[derive(Clone, Debug, Default)]
+struct SourceMap(HashMap<String, config::Value>);
+
+impl config::Source for SourceMap {
+    fn clone_into_box(&self) -> Box<dyn config::Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> Result<config::Map<String, config::Value>, config::ConfigError> {
+        Ok(self.0.clone().into_iter().collect())
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
 struct Settings {
     somebool: bool,
     somestring: String,
+    #[serde(serialize_with = "redact_secret")]
     somesecret: Secret<String>,
     somestruct: SomeStructSettings,
     someoptionalstring: Option<String>,
 }
 
-#[derive(serde::Deserialize, Clone, Debug)]
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
 struct SomeStructSettings {
     #[serde(deserialize_with = "deserialize_number_from_string")]
     someint: u64,
 }
 
+/// Serializes a `Secret<String>` as an empty placeholder, so the `dump` and
+/// `init` subcommands never write secrets to stdout or disk in cleartext.
+fn redact_secret<S: Serializer>(
+    _secret: &Secret<String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str("")
+}
+
 #[derive(Parser, Debug, Clone)]
 #[command(
     name = "config_playground",
@@ -33,12 +78,39 @@ struct Cli {
     #[arg(long, short = 'i')]
     input_file: Option<PathBuf>,
 
+    /// Print which layer (base, profile, user config, runtime file, env, or
+    /// args) each resolved setting came from, instead of running normally
+    #[arg(long)]
+    explain_config: bool,
+
     #[clap(flatten)]
     optional_settings: OptionalSettings,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Print the fully-resolved settings to stdout
+    Dump {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Toml)]
+        format: OutputFormat,
+    },
+    /// Write a starter settings file to the user config directory, unless one is already there
+    Init,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum OutputFormat {
+    Toml,
+    Json,
+    Yaml,
 }
 
 #[skip_serializing_none]
-#[derive(serde::Serialize, Clone, Debug, Args)]
+#[derive(serde::Serialize, Clone, Debug, Args, ConfigSource)]
 struct OptionalSettings {
     /// somestring setting
     #[arg(long)]
@@ -54,46 +126,59 @@ struct OptionalSettings {
 ///             `../configuraton/base.yaml`
 ///        Included as a &str, this config is complete enough to allow the
 ///        application to function without any runtime config file.
-///     2. **Runtime config file** : Parsed at runtime from:
-///             `./configuration/settings.yaml`
+///     2. **Environment profile** : Parsed at runtime from:
+///             `./configuration/{profile}.{toml,yaml,yml,json,ron,json5,env}`
+///        `{profile}` comes from `{APP_NAME}_ENVIRONMENT`, defaulting to
+///        `local` when unset. Lets a single binary ship `base.toml` plus
+///        per-environment overlays (`local.toml`, `production.toml`, ...)
+///        without recompiling. Not guaranteed to be present.
+///     3. **User config directory** : Parsed at runtime from the platform
+///        config directory (via the `directories` crate), e.g.
+///             `~/.config/config_playground/settings.toml` on Linux,
+///             `%APPDATA%\config_playground\config\settings.toml` on Windows, or
+///             `~/Library/Application Support/config_playground/settings.toml` on macOS.
+///        Gives installed binaries a proper per-user config location, since
+///        they aren't necessarily run from a source tree. Not guaranteed to
+///        be present.
+///     4. **Runtime config file** : Parsed at runtime from:
+///             `./configuration/settings.{toml,yaml,yml,json,ron,json5,env}`
 ///        Not guarenteed to be present, but can be convenient when making
-///        major deviations from baseline.
-///     3. **Environment variables** : These are typically where you will find
+///        major deviations from baseline. Takes priority over the user config
+///        directory so local development always wins.
+///
+///        The profile, user config, and runtime layers all resolve their
+///        format from whichever extension is present on disk (see
+///        `resolve_file_source`), rather than assuming TOML; `{APP_NAME}_CONFIG_FORMAT`
+///        pins it to one extension instead of probing. `.env`-formatted files
+///        are parsed by `EnvFileFormat`, a small custom `config::Format` impl,
+///        to demonstrate that the stack isn't limited to `config-rs`'s
+///        built-in formats.
+///     5. **Environment variables** : These are typically where you will find
 ///        API secrets, database connection params, etc. We prefix our env-vars
 ///        with the (shouty-snake converted) cargo-provided app name instead of
 ///        using the more generic "APP" to prevent collisions.
-///     4. **(Optional) Input arguments** : These are optional settings passed
+///     6. **(Optional) Input arguments** : These are optional settings passed
 ///        in by the caller upon execution. In this CLI example the user could
 ///        pass these settings as input arguments, but this same idea holds for
 ///        other types of applications, such as a lambda/cloud-function that
 ///        receives some query params at startup.
 ///        The `config` crate isn't really designed to source values from
-///        structs (though this would be a great `derive` macro!), so we instead
-///        leverage the ability to add a 'file' from a serde-serialized JSON
-///        string of our `OptionalSettings` struct. This has the added benefit
-///        of stripping out any optional fields that were never set.
+///        structs, so `OptionalSettings` derives `ConfigSource` (see
+///        `config_playground_derive`), which builds a `config::Source` map
+///        directly from its fields and skips any optional field that was
+///        never set.
 ///
 /// See [Rain's Rust CLI recommendations][1]
 /// [1]: https://rust-cli-recommendations.sunshowers.io/configuration.html
 ///
 fn get_configuration(optional_settings: OptionalSettings) -> anyhow::Result<Settings> {
-    static BASE_CFG: &str = include_str!("../configuration/base.toml");
-    static APP_NAME: &str = convert_ascii_case!(shouty_snake, std::env!("CARGO_PKG_NAME"));
-
-    let runtime_path = std::env::current_dir().context("Failed to determine current directory")?;
-    let runtime_cfg = runtime_path.join("configuration/settings.toml");
-    // kindof hacky, but seems to be the easiest solution...
-    let input_cfg = serde_json::to_string(&optional_settings)
-        .context("Couldn't parse user provided settings")?;
+    let sources: Vec<Box<dyn config::Source + Send + Sync>> = config_layers(optional_settings)?
+        .into_iter()
+        .map(|layer| layer.source)
+        .collect();
 
     let settings = config::Config::builder()
-        .add_source(config::File::from_str(BASE_CFG, config::FileFormat::Toml))
-        .add_source(config::File::from(runtime_cfg))
-        .add_source(config::Environment::with_prefix(APP_NAME).separator("__"))
-        .add_source(config::File::from_str(
-            input_cfg.as_str(),
-            config::FileFormat::Json,
-        ))
+        .add_source(sources)
         .build()
         .context("Couldn't build settings")?;
 
@@ -102,10 +187,298 @@ fn get_configuration(optional_settings: OptionalSettings) -> anyhow::Result<Sett
         .context("Error deserializing settings")
 }
 
+/// Resolves the per-user config directory (e.g. `~/.config/config_playground/`
+/// on Linux), if a home directory could be determined for the platform.
+fn user_config_dir() -> Option<PathBuf> {
+    ProjectDirs::from("", "", env!("CARGO_PKG_NAME"))
+        .map(|project_dirs| project_dirs.config_dir().to_path_buf())
+}
+
+/// A single entry in the config layer stack, named so it can be reported back
+/// to the user (see [`get_configuration_with_trace`]).
+struct Layer {
+    name: &'static str,
+    source: Box<dyn config::Source + Send + Sync>,
+}
+
+/// Builds the ordered list of config layers described in [`get_configuration`]'s
+/// doc comment, lowest priority first.
+fn config_layers(optional_settings: OptionalSettings) -> anyhow::Result<Vec<Layer>> {
+    static BASE_CFG: &str = include_str!("../configuration/base.toml");
+
+    let environment =
+        std::env::var(format!("{APP_NAME}_ENVIRONMENT")).unwrap_or_else(|_| "local".to_string());
+
+    let runtime_dir = std::env::current_dir()
+        .context("Failed to determine current directory")?
+        .join("configuration");
+
+    let mut layers = vec![Layer {
+        name: "base",
+        source: Box::new(config::File::from_str(BASE_CFG, config::FileFormat::Toml)),
+    }];
+
+    if let Some(source) = resolve_file_source(&runtime_dir, &environment) {
+        layers.push(Layer {
+            name: "profile",
+            source,
+        });
+    }
+
+    if let Some(user_dir) = user_config_dir() {
+        if let Some(source) = resolve_file_source(&user_dir, "settings") {
+            layers.push(Layer {
+                name: "user config",
+                source,
+            });
+        }
+    }
+
+    if let Some(source) = resolve_file_source(&runtime_dir, "settings") {
+        layers.push(Layer {
+            name: "runtime file",
+            source,
+        });
+    }
+
+    layers.push(Layer {
+        name: "env",
+        source: Box::new(config::Environment::with_prefix(APP_NAME).separator("__")),
+    });
+    layers.push(Layer {
+        name: "args",
+        source: Box::new(optional_settings.into_source()),
+    });
+
+    Ok(layers)
+}
+
+/// File extensions recognized for the runtime and user-config layers, tried in
+/// this order against `{stem}.{ext}` until one exists. An `EnvFileFormat` entry
+/// (`.env`) demonstrates that the stack isn't limited to `config-rs`'s built-in
+/// `FileFormat`s.
+const CONFIG_EXTENSIONS: &[&str] = &["toml", "yaml", "yml", "json", "ron", "json5", "env"];
+
+fn builtin_format_for_extension(ext: &str) -> Option<config::FileFormat> {
+    match ext {
+        "toml" => Some(config::FileFormat::Toml),
+        "yaml" | "yml" => Some(config::FileFormat::Yaml),
+        "json" => Some(config::FileFormat::Json),
+        "ron" => Some(config::FileFormat::Ron),
+        "json5" => Some(config::FileFormat::Json5),
+        _ => None,
+    }
+}
+
+/// A minimal custom `config::Format` for `KEY=value` `.env`-style files, so the
+/// extension-detection below has a non-built-in format to dispatch to.
+#[derive(Clone, Debug)]
+struct EnvFileFormat;
+
+impl config::Format for EnvFileFormat {
+    fn parse(
+        &self,
+        uri: Option<&String>,
+        text: &str,
+    ) -> Result<config::Map<String, config::Value>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut map = config::Map::new();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some((key, value)) = line.split_once('=') {
+                map.insert(
+                    key.trim().to_lowercase(),
+                    config::Value::new(uri, value.trim().to_string()),
+                );
+            }
+        }
+        Ok(map)
+    }
+}
+
+impl config::FileStoredFormat for EnvFileFormat {
+    fn file_extensions(&self) -> &'static [&'static str] {
+        &["env"]
+    }
+}
+
+/// Finds `{dir}/{stem}.{ext}` for the first extension in `CONFIG_EXTENSIONS` that
+/// exists on disk (or, if `{APP_NAME}_CONFIG_FORMAT` is set, just that one
+/// extension), and builds the matching `config::Source`. Lets users supply
+/// `settings.yaml` or `settings.json5` wherever `settings.toml` was expected.
+fn resolve_file_source(dir: &Path, stem: &str) -> Option<Box<dyn config::Source + Send + Sync>> {
+    if let Ok(ext) = std::env::var(format!("{APP_NAME}_CONFIG_FORMAT")) {
+        return build_file_source(dir, stem, &ext);
+    }
+
+    CONFIG_EXTENSIONS
+        .iter()
+        .find_map(|ext| build_file_source(dir, stem, ext))
+}
+
+/// Builds the source for `{dir}/{stem}.{ext}`, or `None` if it doesn't exist
+/// (or `ext` isn't a format this app knows how to parse).
+fn build_file_source(
+    dir: &Path,
+    stem: &str,
+    ext: &str,
+) -> Option<Box<dyn config::Source + Send + Sync>> {
+    let path = dir.join(format!("{stem}.{ext}"));
+    if !path.is_file() {
+        return None;
+    }
+
+    if ext == "env" {
+        let text = std::fs::read_to_string(&path).ok()?;
+        Some(Box::new(config::File::from_str(&text, EnvFileFormat)) as Box<_>)
+    } else {
+        let format = builtin_format_for_extension(ext)?;
+        Some(Box::new(config::File::from(path).format(format)) as Box<_>)
+    }
+}
+
+/// One resolved setting's path, final value, and the layer name that won it.
+type TraceEntry = (String, config::Value, &'static str);
+
+/// Like [`get_configuration`], but also reports which layer won for each
+/// resolved key, mirroring Cargo's config value origin tracking. Backs the
+/// `--explain-config` CLI flag.
+fn get_configuration_with_trace(
+    optional_settings: OptionalSettings,
+) -> anyhow::Result<(Settings, Vec<TraceEntry>)> {
+    let mut trace: HashMap<String, (config::Value, &'static str)> = HashMap::new();
+    let mut sources: Vec<Box<dyn config::Source + Send + Sync>> = Vec::new();
+
+    for layer in config_layers(optional_settings)? {
+        let collected = layer
+            .source
+            .collect()
+            .with_context(|| format!("Couldn't collect '{}' layer", layer.name))?;
+        let mut flattened = HashMap::new();
+        flatten_into(String::new(), collected, &mut flattened);
+        for (path, value) in flattened {
+            trace.insert(path, (value, layer.name));
+        }
+
+        sources.push(layer.source);
+    }
+
+    let settings = config::Config::builder()
+        .add_source(sources)
+        .build()
+        .context("Couldn't build settings")?
+        .try_deserialize::<Settings>()
+        .context("Error deserializing settings")?;
+
+    let mut report: Vec<_> = trace
+        .into_iter()
+        .map(|(path, (value, layer))| (path, value, layer))
+        .collect();
+    report.sort_by(|a, b| a.0.cmp(&b.0));
+
+    Ok((settings, report))
+}
+
+/// Recursively walks a collected table of `config::Value`s, writing each leaf
+/// into `out` keyed by its dotted path (e.g. `somestruct.someint`).
+fn flatten_into(
+    prefix: String,
+    table: HashMap<String, config::Value>,
+    out: &mut HashMap<String, config::Value>,
+) {
+    for (key, value) in table {
+        let path = if prefix.is_empty() {
+            key
+        } else {
+            format!("{prefix}.{key}")
+        };
+        match value {
+            config::Value {
+                kind: config::ValueKind::Table(nested),
+                ..
+            } => flatten_into(path, nested, out),
+            other => {
+                out.insert(path, other);
+            }
+        }
+    }
+}
+
+/// Serializes the fully-resolved `Settings` to the requested format.
+fn dump_settings(settings: &Settings, format: OutputFormat) -> anyhow::Result<String> {
+    match format {
+        OutputFormat::Toml => toml::to_string_pretty(settings).context("Couldn't serialize TOML"),
+        OutputFormat::Json => {
+            serde_json::to_string_pretty(settings).context("Couldn't serialize JSON")
+        }
+        OutputFormat::Yaml => serde_yaml::to_string(settings).context("Couldn't serialize YAML"),
+    }
+}
+
+/// Writes the compiled-in baseline config to the user's config path, unless a
+/// file is already there. Secrets are redacted via `Settings`'s `Serialize`
+/// impl so nothing sensitive lands on disk.
+fn init_user_config() -> anyhow::Result<()> {
+    let user_cfg = user_config_dir()
+        .context("Couldn't determine a user config directory for this platform")?
+        .join("settings.toml");
+
+    if user_cfg.exists() {
+        println!("{} already exists, leaving it alone", user_cfg.display());
+        return Ok(());
+    }
+
+    static BASE_CFG: &str = include_str!("../configuration/base.toml");
+    let settings: Settings =
+        toml::from_str(BASE_CFG).context("Couldn't parse compiled-in base config")?;
+    let redacted = toml::to_string_pretty(&settings).context("Couldn't serialize base config")?;
+
+    if let Some(parent) = user_cfg.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Couldn't create {}", parent.display()))?;
+    }
+    std::fs::write(&user_cfg, redacted)
+        .with_context(|| format!("Couldn't write {}", user_cfg.display()))?;
+
+    println!("Wrote starter config to {}", user_cfg.display());
+    Ok(())
+}
+
 /// This program is a playground for testing configuration layering.
 fn main() {
-    let optional_settings = Cli::parse().optional_settings;
-    let settings = get_configuration(optional_settings).expect("Failed to parse configuration");
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Command::Dump { format }) => {
+            let settings =
+                get_configuration(cli.optional_settings).expect("Failed to parse configuration");
+            let dumped = dump_settings(&settings, format).expect("Failed to serialize settings");
+            println!("{dumped}");
+            return;
+        }
+        Some(Command::Init) => {
+            init_user_config().expect("Failed to write starter config");
+            return;
+        }
+        None => {}
+    }
+
+    if cli.explain_config {
+        let (settings, report) = get_configuration_with_trace(cli.optional_settings)
+            .expect("Failed to parse configuration");
+
+        println!("{:<30} {:<15} VALUE", "KEY", "LAYER");
+        for (path, value, layer) in report {
+            println!("{path:<30} {layer:<15} {:?}", value);
+        }
+        println!("Settings: {:?}", settings);
+        return;
+    }
+
+    let settings =
+        get_configuration(cli.optional_settings).expect("Failed to parse configuration");
 
     println!("Settings: {:?}", settings);
     println!("Secret: {}", settings.somesecret.expose_secret());